@@ -1,20 +1,69 @@
 use crate::settings::PostProcessProvider;
+use futures::stream::{Stream, StreamExt};
 use log::debug;
-use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE, REFERER, USER_AGENT};
+use reqwest::header::{
+    HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE, REFERER, RETRY_AFTER, USER_AGENT,
+};
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
 use std::io::Cursor;
+use std::sync::Mutex;
+use std::time::Duration;
 use hound::{WavSpec, WavWriter};
 
-#[derive(Debug, Serialize)]
+/// Default request timeout used when a provider doesn't specify one.
+const DEFAULT_TIMEOUT_SECS: u64 = 30;
+/// Default retry count used for requests that aren't driven by `PostProcessProvider`.
+const DEFAULT_MAX_RETRIES: u32 = 3;
+/// Base of the exponential backoff between retries, before jitter is added.
+const BASE_BACKOFF_MS: u64 = 500;
+
+#[derive(Debug, Clone, Serialize)]
 struct ChatMessage {
     role: String,
-    content: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_calls: Option<Vec<ToolCall>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_call_id: Option<String>,
+}
+
+impl ChatMessage {
+    fn user(content: String) -> Self {
+        Self {
+            role: "user".to_string(),
+            content: Some(content),
+            tool_calls: None,
+            tool_call_id: None,
+        }
+    }
+}
+
+/// A function call the model asked to invoke, round-tripped verbatim from
+/// the assistant message that requested it back into the `tool` message
+/// carrying its result.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ToolCall {
+    id: String,
+    #[serde(rename = "type")]
+    kind: String,
+    function: ToolCallFunction,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ToolCallFunction {
+    name: String,
+    arguments: String,
 }
 
 #[derive(Debug, Serialize)]
 struct ChatCompletionRequest {
     model: String,
     messages: Vec<ChatMessage>,
+    stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tools: Option<Vec<ToolDefinition>>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -30,10 +79,266 @@ struct ChatChoice {
 #[derive(Debug, Deserialize)]
 struct ChatMessageResponse {
     content: Option<String>,
+    tool_calls: Option<Vec<ToolCall>>,
+}
+
+/// A single Server-Sent-Events chunk from a streaming chat completion.
+#[derive(Debug, Deserialize)]
+struct ChatCompletionChunk {
+    choices: Vec<ChatChunkChoice>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatChunkChoice {
+    delta: ChatDelta,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatDelta {
+    content: Option<String>,
+}
+
+/// How a provider authenticates requests and shapes its endpoint URLs.
+/// `build_headers` dispatches to one of these instead of hardcoding an
+/// auth style per provider, so providers with their own auth flow (like
+/// Vertex AI's OAuth exchange) or URL shape only need to implement it.
+#[async_trait::async_trait]
+trait ApiAuth: Send + Sync {
+    /// Add this provider's auth header(s) to `headers`.
+    async fn apply_auth(&self, headers: &mut HeaderMap, api_key: &str) -> Result<(), String>;
+
+    /// Build the full URL for a chat-completion request to `model`.
+    fn chat_completions_url(&self, base_url: &str, model: &str) -> String {
+        let _ = model;
+        format!("{}/chat/completions", base_url.trim_end_matches('/'))
+    }
+}
+
+/// `Authorization: Bearer <api_key>` — the default for OpenAI-compatible APIs.
+struct BearerAuth;
+
+#[async_trait::async_trait]
+impl ApiAuth for BearerAuth {
+    async fn apply_auth(&self, headers: &mut HeaderMap, api_key: &str) -> Result<(), String> {
+        if api_key.is_empty() {
+            return Ok(());
+        }
+        headers.insert(
+            AUTHORIZATION,
+            HeaderValue::from_str(&format!("Bearer {}", api_key))
+                .map_err(|e| format!("Invalid authorization header value: {}", e))?,
+        );
+        Ok(())
+    }
+}
+
+/// `x-api-key` + `anthropic-version` — Anthropic's native auth style.
+struct AnthropicAuth;
+
+#[async_trait::async_trait]
+impl ApiAuth for AnthropicAuth {
+    async fn apply_auth(&self, headers: &mut HeaderMap, api_key: &str) -> Result<(), String> {
+        if api_key.is_empty() {
+            return Ok(());
+        }
+        headers.insert(
+            "x-api-key",
+            HeaderValue::from_str(api_key)
+                .map_err(|e| format!("Invalid API key header value: {}", e))?,
+        );
+        headers.insert("anthropic-version", HeaderValue::from_static("2023-06-01"));
+        Ok(())
+    }
+}
+
+/// A cached OAuth access token, along with when it stops being usable.
+struct CachedToken {
+    access_token: String,
+    expires_at: std::time::Instant,
+}
+
+static VERTEX_TOKEN_CACHE: std::sync::OnceLock<Mutex<HashMap<String, CachedToken>>> =
+    std::sync::OnceLock::new();
+
+fn vertex_token_cache() -> &'static Mutex<HashMap<String, CachedToken>> {
+    VERTEX_TOKEN_CACHE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// OAuth2 service-account auth for Google Vertex AI / Gemini. The `api_key`
+/// passed in is actually the service account's JSON credentials; the
+/// short-lived access token they're exchanged for is cached (keyed on the
+/// credentials themselves) until shortly before it expires.
+struct VertexAuth {
+    project_id: String,
+    location: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ServiceAccountKey {
+    client_email: String,
+    private_key: String,
+    token_uri: String,
+}
+
+#[derive(Debug, Serialize)]
+struct VertexJwtClaims {
+    iss: String,
+    scope: String,
+    aud: String,
+    iat: u64,
+    exp: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct VertexTokenResponse {
+    access_token: String,
+    expires_in: u64,
+}
+
+impl VertexAuth {
+    fn new(project_id: String, location: String) -> Self {
+        Self {
+            project_id,
+            location,
+        }
+    }
+
+    /// Sign a JWT with the service account's private key and exchange it
+    /// at Google's token endpoint for a short-lived OAuth2 access token.
+    async fn fetch_access_token(service_account_json: &str) -> Result<(String, u64), String> {
+        let key: ServiceAccountKey = serde_json::from_str(service_account_json)
+            .map_err(|e| format!("Invalid Vertex AI service account JSON: {}", e))?;
+
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_err(|e| format!("System clock error: {}", e))?
+            .as_secs();
+
+        let claims = VertexJwtClaims {
+            iss: key.client_email.clone(),
+            scope: "https://www.googleapis.com/auth/cloud-platform".to_string(),
+            aud: key.token_uri.clone(),
+            iat: now,
+            exp: now + 3600,
+        };
+
+        let encoding_key = jsonwebtoken::EncodingKey::from_rsa_pem(key.private_key.as_bytes())
+            .map_err(|e| format!("Invalid service account private key: {}", e))?;
+        let jwt = jsonwebtoken::encode(
+            &jsonwebtoken::Header::new(jsonwebtoken::Algorithm::RS256),
+            &claims,
+            &encoding_key,
+        )
+        .map_err(|e| format!("Failed to sign service account JWT: {}", e))?;
+
+        let client = reqwest::Client::new();
+        let response = client
+            .post(&key.token_uri)
+            .form(&[
+                ("grant_type", "urn:ietf:params:oauth:grant-type:jwt-bearer"),
+                ("assertion", &jwt),
+            ])
+            .send()
+            .await
+            .map_err(|e| format!("Vertex AI token exchange request failed: {}", e))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let error_text = response
+                .text()
+                .await
+                .unwrap_or_else(|_| "Failed to read error response".to_string());
+            return Err(format!(
+                "Vertex AI token exchange failed ({}): {}",
+                status, error_text
+            ));
+        }
+
+        let token: VertexTokenResponse = response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse Vertex AI token response: {}", e))?;
+
+        Ok((token.access_token, token.expires_in))
+    }
+
+    /// Return a cached access token for this service account, refreshing
+    /// it if it's missing or close to expiry.
+    async fn access_token(service_account_json: &str) -> Result<String, String> {
+        const EXPIRY_SAFETY_MARGIN: Duration = Duration::from_secs(60);
+
+        if let Some(cached) = vertex_token_cache()
+            .lock()
+            .unwrap()
+            .get(service_account_json)
+        {
+            if cached.expires_at > std::time::Instant::now() + EXPIRY_SAFETY_MARGIN {
+                return Ok(cached.access_token.clone());
+            }
+        }
+
+        let (access_token, expires_in) = Self::fetch_access_token(service_account_json).await?;
+        let expires_at = std::time::Instant::now() + Duration::from_secs(expires_in);
+
+        vertex_token_cache().lock().unwrap().insert(
+            service_account_json.to_string(),
+            CachedToken {
+                access_token: access_token.clone(),
+                expires_at,
+            },
+        );
+
+        Ok(access_token)
+    }
+}
+
+#[async_trait::async_trait]
+impl ApiAuth for VertexAuth {
+    async fn apply_auth(&self, headers: &mut HeaderMap, api_key: &str) -> Result<(), String> {
+        let access_token = Self::access_token(api_key).await?;
+        headers.insert(
+            AUTHORIZATION,
+            HeaderValue::from_str(&format!("Bearer {}", access_token))
+                .map_err(|e| format!("Invalid authorization header value: {}", e))?,
+        );
+        Ok(())
+    }
+
+    fn chat_completions_url(&self, base_url: &str, model: &str) -> String {
+        let _ = model;
+        // Deliberate deviation from native `:generateContent`: that endpoint
+        // takes a Gemini-shaped request (`contents`, not `messages`) and
+        // returns `{ candidates: [...] }`, not `{ choices: [...] }`. This
+        // module has no Gemini translation layer, so pointing at it would
+        // authenticate successfully and then fail to serialize/parse every
+        // call. Targeting Vertex's OpenAI-compatible endpoint instead keeps
+        // the existing `ChatCompletionRequest`/`ChatCompletionResponse`
+        // types working unchanged. Native Gemini `generateContent` (and
+        // anything only it exposes, e.g. `thinking_config`) is not supported
+        // by this provider until that translation layer is written.
+        format!(
+            "{}/v1/projects/{}/locations/{}/endpoints/openapi/chat/completions",
+            base_url.trim_end_matches('/'),
+            self.project_id,
+            self.location,
+        )
+    }
+}
+
+/// Resolve the auth/URL strategy for a configured provider.
+fn auth_for_provider(provider: &PostProcessProvider) -> Box<dyn ApiAuth> {
+    match provider.id.as_str() {
+        "anthropic" => Box::new(AnthropicAuth),
+        "vertex" | "vertex-ai" => Box::new(VertexAuth::new(
+            provider.vertex_project_id.clone(),
+            provider.vertex_location.clone(),
+        )),
+        _ => Box::new(BearerAuth),
+    }
 }
 
 /// Build headers for API requests based on provider type
-fn build_headers(provider: &PostProcessProvider, api_key: &str) -> Result<HeaderMap, String> {
+async fn build_headers(provider: &PostProcessProvider, api_key: &str) -> Result<HeaderMap, String> {
     let mut headers = HeaderMap::new();
 
     // Common headers
@@ -48,36 +353,188 @@ fn build_headers(provider: &PostProcessProvider, api_key: &str) -> Result<Header
     );
     headers.insert("X-Title", HeaderValue::from_static("Handy"));
 
-    // Provider-specific auth headers
-    if !api_key.is_empty() {
-        if provider.id == "anthropic" {
-            headers.insert(
-                "x-api-key",
-                HeaderValue::from_str(api_key)
-                    .map_err(|e| format!("Invalid API key header value: {}", e))?,
-            );
-            headers.insert("anthropic-version", HeaderValue::from_static("2023-06-01"));
-        } else {
-            headers.insert(
-                AUTHORIZATION,
-                HeaderValue::from_str(&format!("Bearer {}", api_key))
-                    .map_err(|e| format!("Invalid authorization header value: {}", e))?,
-            );
-        }
-    }
+    auth_for_provider(provider)
+        .apply_auth(&mut headers, api_key)
+        .await?;
 
     Ok(headers)
 }
 
-/// Create an HTTP client with provider-specific headers
-fn create_client(provider: &PostProcessProvider, api_key: &str) -> Result<reqwest::Client, String> {
-    let headers = build_headers(provider, api_key)?;
+/// Create an HTTP client with provider-specific headers and timeout
+async fn create_client(provider: &PostProcessProvider, api_key: &str) -> Result<reqwest::Client, String> {
+    let headers = build_headers(provider, api_key).await?;
+    let timeout_secs = if provider.request_timeout_secs > 0 {
+        provider.request_timeout_secs
+    } else {
+        DEFAULT_TIMEOUT_SECS
+    };
     reqwest::Client::builder()
         .default_headers(headers)
+        .timeout(Duration::from_secs(timeout_secs))
         .build()
         .map_err(|e| format!("Failed to build HTTP client: {}", e))
 }
 
+/// Create an HTTP client for a streaming response. `.timeout()` bounds the
+/// whole request including the body read, which would cut off a long SSE
+/// stream partway through; only the connection setup is bounded here so a
+/// long-running transcript can keep streaming.
+async fn create_streaming_client(
+    provider: &PostProcessProvider,
+    api_key: &str,
+) -> Result<reqwest::Client, String> {
+    let headers = build_headers(provider, api_key).await?;
+    let connect_timeout_secs = if provider.request_timeout_secs > 0 {
+        provider.request_timeout_secs
+    } else {
+        DEFAULT_TIMEOUT_SECS
+    };
+    reqwest::Client::builder()
+        .default_headers(headers)
+        .connect_timeout(Duration::from_secs(connect_timeout_secs))
+        .build()
+        .map_err(|e| format!("Failed to build HTTP client: {}", e))
+}
+
+/// Returns true for status codes worth retrying: rate limits and transient
+/// server errors. Client errors like 400/401 are never retried.
+fn is_retryable_status(status: reqwest::StatusCode) -> bool {
+    matches!(status.as_u16(), 429 | 500 | 502 | 503 | 504)
+}
+
+/// A cheap, non-cryptographic source of jitter, so backoff doesn't need a
+/// dedicated RNG dependency just to avoid retries synchronizing.
+fn jitter_millis(max: u64) -> u64 {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.subsec_nanos() as u64)
+        .unwrap_or(0);
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+
+    nanos.wrapping_add(count.wrapping_mul(0x9E37_79B9_7F4A_7C15)) % max.max(1)
+}
+
+/// Exponential backoff with jitter for the given (zero-based) retry attempt.
+fn backoff_delay(attempt: u32) -> Duration {
+    let exponential = BASE_BACKOFF_MS.saturating_mul(1u64 << attempt.min(6));
+    let jitter = jitter_millis(BASE_BACKOFF_MS);
+    Duration::from_millis(exponential + jitter)
+}
+
+/// Parse a `Retry-After` header into a wait duration, if present. Per RFC
+/// 7231 the value is either a delay in seconds or an HTTP-date; both forms
+/// are accepted.
+fn parse_retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?;
+
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+
+    parse_http_date(value)?
+        .duration_since(std::time::SystemTime::now())
+        .ok()
+}
+
+/// Parse an RFC 7231 IMF-fixdate (the form servers use for `Retry-After`,
+/// e.g. "Wed, 21 Oct 2015 07:28:00 GMT") into a `SystemTime`. Written by
+/// hand rather than pulling in a date crate for one header.
+fn parse_http_date(value: &str) -> Option<std::time::SystemTime> {
+    let mut parts = value.split_whitespace();
+    parts.next()?; // weekday, e.g. "Wed,", ignored
+    let day: u64 = parts.next()?.parse().ok()?;
+    let month = match parts.next()? {
+        "Jan" => 1,
+        "Feb" => 2,
+        "Mar" => 3,
+        "Apr" => 4,
+        "May" => 5,
+        "Jun" => 6,
+        "Jul" => 7,
+        "Aug" => 8,
+        "Sep" => 9,
+        "Oct" => 10,
+        "Nov" => 11,
+        "Dec" => 12,
+        _ => return None,
+    };
+    let year: i64 = parts.next()?.parse().ok()?;
+
+    let mut time = parts.next()?.split(':');
+    let hour: u64 = time.next()?.parse().ok()?;
+    let minute: u64 = time.next()?.parse().ok()?;
+    let second: u64 = time.next()?.parse().ok()?;
+
+    let days = days_from_civil(year, month, day as u32);
+    let epoch_secs = (days as u64) * 86_400 + hour * 3600 + minute * 60 + second;
+    Some(std::time::UNIX_EPOCH + Duration::from_secs(epoch_secs))
+}
+
+/// Days since the Unix epoch for a proleptic-Gregorian (year, month, day).
+/// Howard Hinnant's `days_from_civil` algorithm, valid for all dates this
+/// header will ever carry.
+fn days_from_civil(y: i64, m: u32, d: u32) -> i64 {
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = if y >= 0 { y } else { y - 399 } / 400;
+    let yoe = (y - era * 400) as u64;
+    let mp = (u64::from(m) + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + u64::from(d) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe as i64 - 719_468
+}
+
+/// Send a request built by `build_request`, retrying idempotent failures
+/// (429/500/502/503/504, network timeouts and connection errors) up to `max_retries`
+/// times with exponential backoff plus jitter, honoring `Retry-After` when
+/// the server provides one. Non-retryable statuses (e.g. 400/401) and
+/// non-network errors are returned immediately so callers can surface them.
+async fn send_with_retry<F>(build_request: F, max_retries: u32) -> Result<reqwest::Response, String>
+where
+    F: Fn() -> reqwest::RequestBuilder,
+{
+    let mut attempt = 0;
+    loop {
+        match build_request().send().await {
+            Ok(response) => {
+                let status = response.status();
+                if status.is_success() || !is_retryable_status(status) || attempt >= max_retries {
+                    return Ok(response);
+                }
+
+                let delay = parse_retry_after(response.headers()).unwrap_or_else(|| backoff_delay(attempt));
+                debug!(
+                    "Retrying request after status {} (attempt {}/{}), waiting {:?}",
+                    status,
+                    attempt + 1,
+                    max_retries,
+                    delay
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            Err(e) => {
+                if attempt >= max_retries || !(e.is_timeout() || e.is_connect()) {
+                    return Err(format!("HTTP request failed: {}", e));
+                }
+
+                let delay = backoff_delay(attempt);
+                debug!(
+                    "Retrying request after network error (attempt {}/{}), waiting {:?}: {}",
+                    attempt + 1,
+                    max_retries,
+                    delay,
+                    e
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+        }
+    }
+}
+
 /// Send a chat completion request to an OpenAI-compatible API
 /// Returns Ok(Some(content)) on success, Ok(None) if response has no content,
 /// or Err on actual errors (HTTP, parsing, etc.)
@@ -87,27 +544,24 @@ pub async fn send_chat_completion(
     model: &str,
     prompt: String,
 ) -> Result<Option<String>, String> {
-    let base_url = provider.base_url.trim_end_matches('/');
-    let url = format!("{}/chat/completions", base_url);
+    let url = auth_for_provider(provider).chat_completions_url(&provider.base_url, model);
 
     debug!("Sending chat completion request to: {}", url);
 
-    let client = create_client(provider, &api_key)?;
+    let client = create_client(provider, &api_key).await?;
 
     let request_body = ChatCompletionRequest {
         model: model.to_string(),
-        messages: vec![ChatMessage {
-            role: "user".to_string(),
-            content: prompt,
-        }],
+        messages: vec![ChatMessage::user(prompt)],
+        stream: false,
+        tools: None,
     };
 
-    let response = client
-        .post(&url)
-        .json(&request_body)
-        .send()
-        .await
-        .map_err(|e| format!("HTTP request failed: {}", e))?;
+    let response = send_with_retry(
+        || client.post(&url).json(&request_body),
+        provider.max_retries,
+    )
+    .await?;
 
     let status = response.status();
     if !status.is_success() {
@@ -132,6 +586,144 @@ pub async fn send_chat_completion(
         .and_then(|choice| choice.message.content.clone()))
 }
 
+/// Drain complete `\n\n`-terminated SSE events out of `buffer`, leaving any
+/// trailing partial event in place for the next chunk to complete.
+fn drain_sse_events(buffer: &mut Vec<u8>) -> Vec<Vec<u8>> {
+    let mut events = Vec::new();
+    while let Some(event_end) = buffer.windows(2).position(|w| w == b"\n\n") {
+        events.push(buffer.drain(..event_end + 2).collect());
+    }
+    events
+}
+
+/// Parse one SSE event's `data: ` lines into content deltas (or parse
+/// errors), along with whether the `[DONE]` sentinel was seen.
+fn parse_sse_event(event: &str) -> (Vec<Result<String, String>>, bool) {
+    let mut deltas = Vec::new();
+    let mut done = false;
+
+    for line in event.lines() {
+        let Some(data) = line.strip_prefix("data: ") else {
+            continue;
+        };
+
+        if data == "[DONE]" {
+            done = true;
+            break;
+        }
+
+        match serde_json::from_str::<ChatCompletionChunk>(data) {
+            Ok(chunk) => {
+                if let Some(content) = chunk
+                    .choices
+                    .first()
+                    .and_then(|choice| choice.delta.content.clone())
+                {
+                    deltas.push(Ok(content));
+                }
+            }
+            Err(e) => deltas.push(Err(format!("Failed to parse SSE chunk: {}", e))),
+        }
+    }
+
+    (deltas, done)
+}
+
+/// Send a chat completion request and stream back incremental content deltas
+/// as they arrive, instead of waiting for the full response body.
+///
+/// The response is expected to be a `text/event-stream` of `data: <json>`
+/// lines terminated by a `data: [DONE]` sentinel, per the OpenAI streaming
+/// format. Deltas are yielded as soon as a full SSE event has been buffered,
+/// so dictated text can be rewritten live instead of appearing all at once.
+pub async fn send_chat_completion_stream(
+    provider: &PostProcessProvider,
+    api_key: String,
+    model: &str,
+    prompt: String,
+) -> Result<impl Stream<Item = Result<String, String>>, String> {
+    let url = auth_for_provider(provider).chat_completions_url(&provider.base_url, model);
+
+    debug!("Sending streaming chat completion request to: {}", url);
+
+    let client = create_streaming_client(provider, &api_key).await?;
+
+    let request_body = ChatCompletionRequest {
+        model: model.to_string(),
+        messages: vec![ChatMessage::user(prompt)],
+        stream: true,
+        tools: None,
+    };
+
+    let response = send_with_retry(
+        || client.post(&url).json(&request_body),
+        provider.max_retries,
+    )
+    .await?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let error_text = response
+            .text()
+            .await
+            .unwrap_or_else(|_| "Failed to read error response".to_string());
+        return Err(format!(
+            "API request failed with status {}: {}",
+            status, error_text
+        ));
+    }
+
+    let byte_stream = response.bytes_stream().boxed();
+
+    // SSE events may span multiple network chunks, and a multibyte UTF-8
+    // character can straddle a chunk boundary. Buffer raw bytes and only
+    // decode a slice once it's a complete `\n\n`-terminated event, so we
+    // never try to decode a character that's been split across two reads.
+    // `pending` holds decoded deltas/errors not yet yielded, since one
+    // buffer flush can produce more than one per network read.
+    let state = (byte_stream, Vec::<u8>::new(), VecDeque::<Result<String, String>>::new(), false);
+
+    Ok(futures::stream::unfold(state, |(mut byte_stream, mut buffer, mut pending, mut done)| async move {
+        loop {
+            if let Some(item) = pending.pop_front() {
+                return Some((item, (byte_stream, buffer, pending, done)));
+            }
+            if done {
+                return None;
+            }
+
+            match byte_stream.next().await {
+                Some(Ok(bytes)) => {
+                    buffer.extend_from_slice(&bytes);
+
+                    for event_bytes in drain_sse_events(&mut buffer) {
+                        match std::str::from_utf8(&event_bytes) {
+                            Ok(event) => {
+                                let (deltas, event_done) = parse_sse_event(event);
+                                pending.extend(deltas);
+                                if event_done {
+                                    done = true;
+                                    break;
+                                }
+                            }
+                            Err(e) => {
+                                pending.push_back(Err(format!("Invalid UTF-8 in SSE event: {}", e)));
+                            }
+                        }
+                    }
+                }
+                Some(Err(e)) => {
+                    pending.push_back(Err(format!("Stream read failed: {}", e)));
+                    done = true;
+                }
+                None => {
+                    done = true;
+                }
+            }
+        }
+    }))
+}
+
 /// Fetch available models from an OpenAI-compatible API
 /// Returns a list of model IDs
 pub async fn fetch_models(
@@ -143,13 +735,9 @@ pub async fn fetch_models(
 
     debug!("Fetching models from: {}", url);
 
-    let client = create_client(provider, &api_key)?;
+    let client = create_client(provider, &api_key).await?;
 
-    let response = client
-        .get(&url)
-        .send()
-        .await
-        .map_err(|e| format!("Failed to fetch models: {}", e))?;
+    let response = send_with_retry(|| client.get(&url), provider.max_retries).await?;
 
     let status = response.status();
     if !status.is_success() {
@@ -192,21 +780,31 @@ pub async fn fetch_models(
     Ok(models)
 }
 
-/// Transcribe audio using OpenAI's transcription API
-pub async fn transcribe_cloud(
-    api_key: &str,
-    base_url: &str,
-    model: &str,
-    audio_samples: Vec<f32>,
-) -> Result<String, String> {
-    if api_key.is_empty() {
-        return Err("OpenAI API key is missing. Please add it in the Advanced settings.".to_string());
-    }
+/// A single word-level timing entry from a transcription provider.
+#[derive(Debug, Clone, Serialize)]
+pub struct TranscriptWord {
+    pub word: String,
+    pub start: f64,
+    pub end: f64,
+    pub confidence: Option<f32>,
+}
+
+/// The result of a cloud transcription: the full text plus, when the
+/// provider reports it, per-word timing for subtitles and click-to-edit.
+#[derive(Debug, Clone, Serialize)]
+pub struct Transcript {
+    pub text: String,
+    pub words: Option<Vec<TranscriptWord>>,
+}
 
-    let url = format!("{}/audio/transcriptions", base_url.trim_end_matches('/'));
-    debug!("Sending cloud transcription request to: {}", url);
+/// A pluggable cloud speech-to-text backend.
+#[async_trait::async_trait]
+pub trait CloudTranscriber {
+    async fn transcribe(&self, audio_samples: Vec<f32>) -> Result<Transcript, String>;
+}
 
-    // Convert f32 samples to WAV bytes in memory
+/// Encode f32 PCM samples as 16-bit mono WAV bytes in memory.
+fn samples_to_wav(audio_samples: &[f32]) -> Result<Vec<u8>, String> {
     let spec = WavSpec {
         channels: 1,
         sample_rate: 16000,
@@ -220,26 +818,363 @@ pub async fn transcribe_cloud(
             .map_err(|e| format!("Failed to create WAV writer: {}", e))?;
         for sample in audio_samples {
             let sample_i16 = (sample * i16::MAX as f32) as i16;
-            writer.write_sample(sample_i16)
+            writer
+                .write_sample(sample_i16)
                 .map_err(|e| format!("Failed to write WAV sample: {}", e))?;
         }
-        writer.finalize().map_err(|e| format!("Failed to finalize WAV file: {}", e))?;
+        writer
+            .finalize()
+            .map_err(|e| format!("Failed to finalize WAV file: {}", e))?;
     }
 
-    let wav_bytes = wav_buffer.into_inner();
+    Ok(wav_buffer.into_inner())
+}
 
-    let client = reqwest::Client::new();
-    let form = reqwest::multipart::Form::new()
-        .part("file", reqwest::multipart::Part::bytes(wav_bytes).file_name("audio.wav").mime_str("audio/wav").map_err(|e| e.to_string())?)
-        .text("model", model.to_string());
+/// Transcribes audio using OpenAI's `/audio/transcriptions` endpoint,
+/// requesting `verbose_json` so segment/word timing comes back alongside
+/// the text.
+pub struct OpenAiTranscriber {
+    pub api_key: String,
+    pub base_url: String,
+    pub model: String,
+    /// Per-request timeout in seconds; `0` falls back to `DEFAULT_TIMEOUT_SECS`.
+    pub request_timeout_secs: u64,
+    /// Maximum retry attempts; `0` falls back to `DEFAULT_MAX_RETRIES`.
+    pub max_retries: u32,
+}
 
-    let response = client
-        .post(&url)
-        .header(AUTHORIZATION, format!("Bearer {}", api_key))
-        .multipart(form)
-        .send()
-        .await
-        .map_err(|e| format!("Cloud transcription request failed: {}", e))?;
+#[async_trait::async_trait]
+impl CloudTranscriber for OpenAiTranscriber {
+    async fn transcribe(&self, audio_samples: Vec<f32>) -> Result<Transcript, String> {
+        if self.api_key.is_empty() {
+            return Err(
+                "OpenAI API key is missing. Please add it in the Advanced settings.".to_string(),
+            );
+        }
+
+        let url = format!(
+            "{}/audio/transcriptions",
+            self.base_url.trim_end_matches('/')
+        );
+        debug!("Sending cloud transcription request to: {}", url);
+
+        let wav_bytes = samples_to_wav(&audio_samples)?;
+
+        let timeout_secs = if self.request_timeout_secs > 0 {
+            self.request_timeout_secs
+        } else {
+            DEFAULT_TIMEOUT_SECS
+        };
+        let max_retries = if self.max_retries > 0 {
+            self.max_retries
+        } else {
+            DEFAULT_MAX_RETRIES
+        };
+
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(timeout_secs))
+            .build()
+            .map_err(|e| format!("Failed to build HTTP client: {}", e))?;
+
+        let response = send_with_retry(
+            || {
+                let form = reqwest::multipart::Form::new()
+                    .part(
+                        "file",
+                        reqwest::multipart::Part::bytes(wav_bytes.clone())
+                            .file_name("audio.wav")
+                            .mime_str("audio/wav")
+                            .expect("audio/wav is a valid mime type"),
+                    )
+                    .text("model", self.model.clone())
+                    .text("response_format", "verbose_json")
+                    // `verbose_json` alone only returns segment timing; word-level
+                    // entries require explicitly requesting the "word" granularity.
+                    .text("timestamp_granularities[]", "word");
+
+                client
+                    .post(&url)
+                    .header(AUTHORIZATION, format!("Bearer {}", self.api_key))
+                    .multipart(form)
+            },
+            max_retries,
+        )
+        .await?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let error_text = response
+                .text()
+                .await
+                .unwrap_or_else(|_| "Failed to read error response".to_string());
+
+            // Try to parse OpenAI error message
+            if let Ok(error_json) = serde_json::from_str::<serde_json::Value>(&error_text) {
+                if let Some(error) = error_json.get("error") {
+                    if let Some(message) = error.get("message").and_then(|m| m.as_str()) {
+                        return Err(format!("OpenAI API Error: {}", message));
+                    }
+                }
+            }
+
+            return Err(format!(
+                "Cloud transcription failed ({}): {}",
+                status, error_text
+            ));
+        }
+
+        #[derive(Deserialize)]
+        struct VerboseTranscriptionResponse {
+            text: String,
+            words: Option<Vec<OpenAiWord>>,
+        }
+
+        #[derive(Deserialize)]
+        struct OpenAiWord {
+            word: String,
+            start: f64,
+            end: f64,
+        }
+
+        let result: VerboseTranscriptionResponse = response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse cloud transcription response: {}", e))?;
+
+        Ok(Transcript {
+            text: result.text,
+            words: result.words.map(|words| {
+                words
+                    .into_iter()
+                    .map(|w| TranscriptWord {
+                        word: w.word,
+                        start: w.start,
+                        end: w.end,
+                        confidence: None,
+                    })
+                    .collect()
+            }),
+        })
+    }
+}
+
+/// Transcribes audio using Deepgram's `listen` endpoint, posting the raw
+/// WAV bytes and reading back per-word confidence and timing.
+pub struct DeepgramTranscriber {
+    pub api_key: String,
+    pub base_url: String,
+    pub model: String,
+    pub language: String,
+    /// Per-request timeout in seconds; `0` falls back to `DEFAULT_TIMEOUT_SECS`.
+    pub request_timeout_secs: u64,
+    /// Maximum retry attempts; `0` falls back to `DEFAULT_MAX_RETRIES`.
+    pub max_retries: u32,
+}
+
+#[async_trait::async_trait]
+impl CloudTranscriber for DeepgramTranscriber {
+    async fn transcribe(&self, audio_samples: Vec<f32>) -> Result<Transcript, String> {
+        if self.api_key.is_empty() {
+            return Err(
+                "Deepgram API key is missing. Please add it in the Advanced settings.".to_string(),
+            );
+        }
+
+        let url = format!(
+            "{}/v1/listen?model={}&punctuate=true&language={}",
+            self.base_url.trim_end_matches('/'),
+            self.model,
+            self.language
+        );
+        debug!("Sending Deepgram transcription request to: {}", url);
+
+        let wav_bytes = samples_to_wav(&audio_samples)?;
+
+        let timeout_secs = if self.request_timeout_secs > 0 {
+            self.request_timeout_secs
+        } else {
+            DEFAULT_TIMEOUT_SECS
+        };
+        let max_retries = if self.max_retries > 0 {
+            self.max_retries
+        } else {
+            DEFAULT_MAX_RETRIES
+        };
+
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(timeout_secs))
+            .build()
+            .map_err(|e| format!("Failed to build HTTP client: {}", e))?;
+
+        let response = send_with_retry(
+            || {
+                client
+                    .post(&url)
+                    .header(AUTHORIZATION, format!("Token {}", self.api_key))
+                    .header(CONTENT_TYPE, "audio/wav")
+                    .body(wav_bytes.clone())
+            },
+            max_retries,
+        )
+        .await?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let error_text = response
+                .text()
+                .await
+                .unwrap_or_else(|_| "Failed to read error response".to_string());
+            return Err(format!(
+                "Deepgram transcription failed ({}): {}",
+                status, error_text
+            ));
+        }
+
+        #[derive(Deserialize)]
+        struct DeepgramResponse {
+            results: DeepgramResults,
+        }
+
+        #[derive(Deserialize)]
+        struct DeepgramResults {
+            channels: Vec<DeepgramChannel>,
+        }
+
+        #[derive(Deserialize)]
+        struct DeepgramChannel {
+            alternatives: Vec<DeepgramAlternative>,
+        }
+
+        #[derive(Deserialize)]
+        struct DeepgramAlternative {
+            transcript: String,
+            words: Option<Vec<DeepgramWord>>,
+        }
+
+        #[derive(Deserialize)]
+        struct DeepgramWord {
+            word: String,
+            start: f64,
+            end: f64,
+            confidence: Option<f32>,
+        }
+
+        let parsed: DeepgramResponse = response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse Deepgram response: {}", e))?;
+
+        let alternative = parsed
+            .results
+            .channels
+            .into_iter()
+            .next()
+            .and_then(|channel| channel.alternatives.into_iter().next())
+            .ok_or_else(|| "Deepgram response had no transcription alternatives".to_string())?;
+
+        Ok(Transcript {
+            text: alternative.transcript,
+            words: alternative.words.map(|words| {
+                words
+                    .into_iter()
+                    .map(|w| TranscriptWord {
+                        word: w.word,
+                        start: w.start,
+                        end: w.end,
+                        confidence: w.confidence,
+                    })
+                    .collect()
+            }),
+        })
+    }
+}
+
+/// Transcribe audio using OpenAI's transcription API, returning just the
+/// text. Kept at its original signature/return type for existing callers;
+/// callers that want word-level timestamps should use [`OpenAiTranscriber`]
+/// (or another [`CloudTranscriber`]) directly for the full [`Transcript`].
+pub async fn transcribe_cloud(
+    api_key: &str,
+    base_url: &str,
+    model: &str,
+    audio_samples: Vec<f32>,
+) -> Result<String, String> {
+    // No `PostProcessProvider` is accepted here (see the doc comment above),
+    // so there's no per-call timeout/retry config to thread through; this
+    // always uses `OpenAiTranscriber`'s defaults. Callers that need
+    // configurable timeout/retries should build an `OpenAiTranscriber`
+    // directly.
+    OpenAiTranscriber {
+        api_key: api_key.to_string(),
+        base_url: base_url.to_string(),
+        model: model.to_string(),
+        request_timeout_secs: 0,
+        max_retries: 0,
+    }
+    .transcribe(audio_samples)
+    .await
+    .map(|transcript| transcript.text)
+}
+
+/// Voices supported by the OpenAI-compatible `/audio/speech` endpoint.
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TtsVoice {
+    Alloy,
+    Echo,
+    Fable,
+    Onyx,
+    Nova,
+    Shimmer,
+}
+
+/// Audio container/codec requested from the speech endpoint.
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TtsFormat {
+    Mp3,
+    Wav,
+    Opus,
+}
+
+#[derive(Debug, Serialize)]
+struct SpeechRequest {
+    model: String,
+    input: String,
+    voice: TtsVoice,
+    response_format: TtsFormat,
+}
+
+/// Synthesize speech from text using an OpenAI-compatible `/audio/speech`
+/// endpoint, returning the raw audio bytes the app can play or save so
+/// corrected/translated dictation can be read back to the user.
+pub async fn synthesize_speech(
+    provider: &PostProcessProvider,
+    api_key: String,
+    model: &str,
+    input: String,
+    voice: TtsVoice,
+    format: TtsFormat,
+) -> Result<Vec<u8>, String> {
+    let base_url = provider.base_url.trim_end_matches('/');
+    let url = format!("{}/audio/speech", base_url);
+
+    debug!("Sending speech synthesis request to: {}", url);
+
+    let client = create_client(provider, &api_key).await?;
+
+    let request_body = SpeechRequest {
+        model: model.to_string(),
+        input,
+        voice,
+        response_format: format,
+    };
+
+    let response = send_with_retry(
+        || client.post(&url).json(&request_body),
+        provider.max_retries,
+    )
+    .await?;
 
     let status = response.status();
     if !status.is_success() {
@@ -247,28 +1182,344 @@ pub async fn transcribe_cloud(
             .text()
             .await
             .unwrap_or_else(|_| "Failed to read error response".to_string());
+        return Err(format!(
+            "Speech synthesis failed ({}): {}",
+            status, error_text
+        ));
+    }
 
-        // Try to parse OpenAI error message
-        if let Ok(error_json) = serde_json::from_str::<serde_json::Value>(&error_text) {
-            if let Some(error) = error_json.get("error") {
-                if let Some(message) = error.get("message").and_then(|m| m.as_str()) {
-                    return Err(format!("OpenAI API Error: {}", message));
-                }
+    response
+        .bytes()
+        .await
+        .map(|bytes| bytes.to_vec())
+        .map_err(|e| format!("Failed to read audio response: {}", e))
+}
+
+/// Maximum number of tool-call round-trips `send_chat_completion_with_tools`
+/// will make before giving up and returning an error.
+const MAX_TOOL_ITERATIONS: u32 = 5;
+
+/// An OpenAI-style function-calling tool definition advertised to the model.
+#[derive(Debug, Clone, Serialize)]
+pub struct ToolDefinition {
+    #[serde(rename = "type")]
+    kind: String,
+    function: ToolFunctionDef,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ToolFunctionDef {
+    name: String,
+    description: String,
+    parameters: serde_json::Value,
+}
+
+impl ToolDefinition {
+    pub fn new(name: impl Into<String>, description: impl Into<String>, parameters: serde_json::Value) -> Self {
+        Self {
+            kind: "function".to_string(),
+            function: ToolFunctionDef {
+                name: name.into(),
+                description: description.into(),
+                parameters,
+            },
+        }
+    }
+}
+
+type ToolHandler =
+    Box<dyn Fn(serde_json::Value) -> futures::future::BoxFuture<'static, Result<String, String>> + Send + Sync>;
+
+/// Maps tool names to local async handlers, so post-processing commands
+/// like "insert the current date" or "format as a bulleted list" can have
+/// the model invoke a real function instead of hallucinating the result.
+#[derive(Default)]
+pub struct ToolRegistry {
+    tools: HashMap<String, (ToolDefinition, ToolHandler)>,
+}
+
+impl ToolRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a tool under its definition's function name.
+    pub fn register<F, Fut>(&mut self, definition: ToolDefinition, handler: F)
+    where
+        F: Fn(serde_json::Value) -> Fut + Send + Sync + 'static,
+        Fut: std::future::Future<Output = Result<String, String>> + Send + 'static,
+    {
+        let name = definition.function.name.clone();
+        self.tools
+            .insert(name, (definition, Box::new(move |args| Box::pin(handler(args)))));
+    }
+
+    fn definitions(&self) -> Vec<ToolDefinition> {
+        self.tools.values().map(|(def, _)| def.clone()).collect()
+    }
+
+    /// Invoke the handler for a model-requested tool call. Errors (unknown
+    /// tool, bad arguments, handler failure) are returned as the tool
+    /// result text rather than aborting the loop, so the model can see
+    /// what went wrong and recover.
+    async fn dispatch(&self, call: &ToolCall) -> String {
+        let Some((_, handler)) = self.tools.get(&call.function.name) else {
+            return format!("Error: unknown tool \"{}\"", call.function.name);
+        };
+
+        let arguments: serde_json::Value = match serde_json::from_str(&call.function.arguments) {
+            Ok(value) => value,
+            Err(e) => {
+                return format!(
+                    "Error: invalid arguments for \"{}\": {}",
+                    call.function.name, e
+                )
             }
+        };
+
+        match handler(arguments).await {
+            Ok(result) => result,
+            Err(e) => format!("Error: {}", e),
+        }
+    }
+}
+
+/// Send a chat completion request with function-calling tools attached,
+/// handling the multi-step tool loop: when the model responds with
+/// `tool_calls` instead of content, dispatch each call through `tools`,
+/// append the results as `role: "tool"` messages, and re-send the
+/// conversation until the model produces a final textual answer.
+pub async fn send_chat_completion_with_tools(
+    provider: &PostProcessProvider,
+    api_key: String,
+    model: &str,
+    prompt: String,
+    tools: &ToolRegistry,
+) -> Result<Option<String>, String> {
+    let url = auth_for_provider(provider).chat_completions_url(&provider.base_url, model);
+    let client = create_client(provider, &api_key).await?;
+    let tool_definitions = tools.definitions();
+
+    let mut messages = vec![ChatMessage::user(prompt)];
+
+    for _ in 0..MAX_TOOL_ITERATIONS {
+        let request_body = ChatCompletionRequest {
+            model: model.to_string(),
+            messages: messages.clone(),
+            stream: false,
+            tools: Some(tool_definitions.clone()),
+        };
+
+        debug!("Sending tool-enabled chat completion request to: {}", url);
+
+        let response = send_with_retry(
+            || client.post(&url).json(&request_body),
+            provider.max_retries,
+        )
+        .await?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let error_text = response
+                .text()
+                .await
+                .unwrap_or_else(|_| "Failed to read error response".to_string());
+            return Err(format!(
+                "API request failed with status {}: {}",
+                status, error_text
+            ));
         }
 
-        return Err(format!("Cloud transcription failed ({}): {}", status, error_text));
+        let completion: ChatCompletionResponse = response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse API response: {}", e))?;
+
+        let Some(choice) = completion.choices.into_iter().next() else {
+            return Ok(None);
+        };
+
+        let Some(calls) = choice.message.tool_calls else {
+            return Ok(choice.message.content);
+        };
+
+        messages.push(ChatMessage {
+            role: "assistant".to_string(),
+            content: choice.message.content,
+            tool_calls: Some(calls.clone()),
+            tool_call_id: None,
+        });
+
+        for call in &calls {
+            let result = tools.dispatch(call).await;
+            messages.push(ChatMessage {
+                role: "tool".to_string(),
+                content: Some(result),
+                tool_calls: None,
+                tool_call_id: Some(call.id.clone()),
+            });
+        }
     }
 
-    #[derive(Deserialize)]
-    struct TranscriptionResponse {
-        text: String,
+    Err(format!(
+        "Exceeded {} tool-call iterations without a final answer",
+        MAX_TOOL_ITERATIONS
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retryable_statuses_are_classified_correctly() {
+        for status in [429, 500, 502, 503, 504] {
+            assert!(is_retryable_status(reqwest::StatusCode::from_u16(status).unwrap()));
+        }
+        for status in [200, 400, 401, 404] {
+            assert!(!is_retryable_status(reqwest::StatusCode::from_u16(status).unwrap()));
+        }
     }
 
-    let result: TranscriptionResponse = response
-        .json()
-        .await
-        .map_err(|e| format!("Failed to parse cloud transcription response: {}", e))?;
+    #[test]
+    fn parse_retry_after_reads_seconds_when_present() {
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("2"));
+        assert_eq!(parse_retry_after(&headers), Some(Duration::from_secs(2)));
+    }
+
+    #[test]
+    fn parse_retry_after_is_none_when_absent_or_invalid() {
+        assert_eq!(parse_retry_after(&HeaderMap::new()), None);
+
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("not-a-number"));
+        assert_eq!(parse_retry_after(&headers), None);
+    }
+
+    #[test]
+    fn parse_retry_after_reads_http_date_form() {
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("Fri, 01 Jan 2100 00:00:00 GMT"));
+        // The delay is the time remaining until that date (epoch second 4_102_444_800).
+        let until = (std::time::UNIX_EPOCH + Duration::from_secs(4_102_444_800))
+            .duration_since(std::time::SystemTime::now())
+            .unwrap();
+        let delay = parse_retry_after(&headers).unwrap();
+        assert!(delay <= until && until - delay < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn days_from_civil_matches_known_epoch_offsets() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(days_from_civil(1970, 1, 2), 1);
+        assert_eq!(days_from_civil(2000, 3, 1), 11_017);
+    }
 
-    Ok(result.text)
+    #[test]
+    fn backoff_delay_grows_and_stays_bounded() {
+        let first = backoff_delay(0);
+        let second = backoff_delay(1);
+        assert!(first.as_millis() >= BASE_BACKOFF_MS as u128);
+        assert!(first.as_millis() < (BASE_BACKOFF_MS * 2) as u128);
+        assert!(second.as_millis() >= (BASE_BACKOFF_MS * 2) as u128);
+        assert!(second.as_millis() < (BASE_BACKOFF_MS * 3) as u128);
+
+        // Attempt is capped internally so delay doesn't grow unbounded.
+        let capped = backoff_delay(20);
+        assert!(capped.as_millis() < (BASE_BACKOFF_MS * 65) as u128);
+    }
+
+    #[test]
+    fn drain_sse_events_splits_complete_events_and_keeps_partial_tail() {
+        let mut buffer = b"data: one\n\ndata: two\n\ndata: partial".to_vec();
+        let events = drain_sse_events(&mut buffer);
+
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0], b"data: one\n\n");
+        assert_eq!(events[1], b"data: two\n\n");
+        assert_eq!(buffer, b"data: partial");
+    }
+
+    #[test]
+    fn parse_sse_event_extracts_content_delta() {
+        let event = "data: {\"choices\":[{\"delta\":{\"content\":\"hi\"}}]}\n\n";
+        let (deltas, done) = parse_sse_event(event);
+
+        assert_eq!(deltas, vec![Ok("hi".to_string())]);
+        assert!(!done);
+    }
+
+    #[test]
+    fn parse_sse_event_detects_done_sentinel() {
+        let (deltas, done) = parse_sse_event("data: [DONE]\n\n");
+        assert!(deltas.is_empty());
+        assert!(done);
+    }
+
+    #[test]
+    fn parse_sse_event_reports_malformed_json_as_error() {
+        let (deltas, done) = parse_sse_event("data: not json\n\n");
+        assert_eq!(deltas.len(), 1);
+        assert!(deltas[0].is_err());
+        assert!(!done);
+    }
+
+    #[tokio::test]
+    async fn tool_registry_dispatch_reports_unknown_tool() {
+        let registry = ToolRegistry::new();
+        let call = ToolCall {
+            id: "call_1".to_string(),
+            kind: "function".to_string(),
+            function: ToolCallFunction {
+                name: "does_not_exist".to_string(),
+                arguments: "{}".to_string(),
+            },
+        };
+
+        let result = registry.dispatch(&call).await;
+        assert_eq!(result, "Error: unknown tool \"does_not_exist\"");
+    }
+
+    #[tokio::test]
+    async fn tool_registry_dispatch_reports_invalid_arguments() {
+        let mut registry = ToolRegistry::new();
+        registry.register(
+            ToolDefinition::new("echo", "Echoes its input", serde_json::json!({})),
+            |args| async move { Ok(args.to_string()) },
+        );
+
+        let call = ToolCall {
+            id: "call_1".to_string(),
+            kind: "function".to_string(),
+            function: ToolCallFunction {
+                name: "echo".to_string(),
+                arguments: "not valid json".to_string(),
+            },
+        };
+
+        let result = registry.dispatch(&call).await;
+        assert!(result.starts_with("Error: invalid arguments for \"echo\":"));
+    }
+
+    #[tokio::test]
+    async fn tool_registry_dispatch_invokes_registered_handler() {
+        let mut registry = ToolRegistry::new();
+        registry.register(
+            ToolDefinition::new("echo", "Echoes its input", serde_json::json!({})),
+            |args| async move { Ok(args.to_string()) },
+        );
+
+        let call = ToolCall {
+            id: "call_1".to_string(),
+            kind: "function".to_string(),
+            function: ToolCallFunction {
+                name: "echo".to_string(),
+                arguments: "{\"x\":1}".to_string(),
+            },
+        };
+
+        let result = registry.dispatch(&call).await;
+        assert_eq!(result, "{\"x\":1}");
+    }
 }
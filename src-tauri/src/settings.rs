@@ -0,0 +1,31 @@
+use serde::{Deserialize, Serialize};
+
+/// Configuration for a single post-processing LLM provider (OpenAI-compatible,
+/// Anthropic, Vertex AI, etc.), as persisted in the app's settings store.
+/// `llm_client` reads these fields to decide how to authenticate, where to
+/// send requests, and how aggressively to retry them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PostProcessProvider {
+    /// Stable identifier selecting the auth/URL strategy (e.g. "openai",
+    /// "anthropic", "vertex"). See `llm_client::auth_for_provider`.
+    pub id: String,
+    pub base_url: String,
+    /// Per-request timeout in seconds. `0` falls back to the client's default.
+    #[serde(default)]
+    pub request_timeout_secs: u64,
+    /// Maximum retry attempts for transient failures (429/5xx, timeouts).
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+    /// Google Cloud project ID, used only when `id` is "vertex"/"vertex-ai".
+    #[serde(default)]
+    pub vertex_project_id: String,
+    /// Google Cloud region (e.g. "us-central1"), used only when `id` is
+    /// "vertex"/"vertex-ai".
+    #[serde(default)]
+    pub vertex_location: String,
+}
+
+fn default_max_retries() -> u32 {
+    3
+}